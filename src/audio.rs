@@ -0,0 +1,491 @@
+/*
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ * SPDX-FileCopyrightText: Copyright 2023-2024 KUNBUS GmbH
+ */
+
+//! Play a siren over ALSA playback and, via a loopback capture device, verify that the tones
+//! were actually produced. Sample rate, channel count, sample format, and the tones themselves
+//! are all driven by `cli::Args`, so the same binary can be retargeted to different audio
+//! hardware without recompiling. A WAV file can be substituted for the synthesized siren, and
+//! the captured loopback can be archived to a WAV file in turn.
+
+use alsa::device_name::HintIter;
+use alsa::pcm::{Access, Format, HwParams, State, IO, PCM};
+use alsa::{Direction, ValueOr};
+use anyhow::{bail, Context};
+use std::thread;
+
+use crate::cli::{Args, SampleFormat};
+use crate::goertzel::tone_energy_ratio;
+use crate::wav;
+
+// number of frames written/read per `writei`/`readi` call
+const BUFFER_SIZE: usize = 1024;
+
+// Minimum fraction of a captured block's energy that must sit at the target frequency for the
+// tone to be considered present. Picked empirically to tolerate loopback noise while still
+// rejecting silence.
+const TONE_DETECTION_THRESHOLD: f32 = 0.15;
+
+/// ALSA format corresponding to a `SampleFormat`.
+fn alsa_format(format: SampleFormat) -> Format {
+    match format {
+        SampleFormat::S16 => Format::s16(),
+        SampleFormat::S32 => Format::s32(),
+        SampleFormat::U8 => Format::U8,
+    }
+}
+
+/// `SampleFormat` corresponding to a WAV file's `bits_per_sample`.
+fn sample_format_for_bits(bits_per_sample: u16) -> anyhow::Result<SampleFormat> {
+    match bits_per_sample {
+        8 => Ok(SampleFormat::U8),
+        16 => Ok(SampleFormat::S16),
+        32 => Ok(SampleFormat::S32),
+        other => bail!("Unsupported WAV bit depth: {other}"),
+    }
+}
+
+fn fill_sine_buffer_s16(buf: &mut [i16], freq: f32, rate: u32, channels: u32, amplitude: f32) {
+    for (i, frame) in buf.chunks_mut(channels as usize).enumerate() {
+        let phase = 2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32;
+        let sample = (phase.sin() * amplitude * i16::MAX as f32) as i16;
+        frame.fill(sample);
+    }
+}
+
+fn fill_sine_buffer_s32(buf: &mut [i32], freq: f32, rate: u32, channels: u32, amplitude: f32) {
+    for (i, frame) in buf.chunks_mut(channels as usize).enumerate() {
+        let phase = 2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32;
+        let sample = (phase.sin() * amplitude * i32::MAX as f32) as i32;
+        frame.fill(sample);
+    }
+}
+
+// ALSA's U8 format is unsigned, so the wave is centered on the middle of the range instead of 0.
+fn fill_sine_buffer_u8(buf: &mut [u8], freq: f32, rate: u32, channels: u32, amplitude: f32) {
+    for (i, frame) in buf.chunks_mut(channels as usize).enumerate() {
+        let phase = 2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32;
+        let sample = (128.0 + phase.sin() * amplitude * i8::MAX as f32) as u8;
+        frame.fill(sample);
+    }
+}
+
+enum PlaybackIo<'a> {
+    S16(IO<'a, i16>),
+    S32(IO<'a, i32>),
+    U8(IO<'a, u8>),
+}
+
+impl PlaybackIo<'_> {
+    /// Generate one tone at `freq` and play it back for `args.duration` seconds.
+    fn play_tone(&self, args: &Args, freq: f32) -> anyhow::Result<()> {
+        let writes = args.duration * args.rate as u64 / BUFFER_SIZE as u64;
+
+        match self {
+            Self::S16(io) => {
+                let mut buf = vec![0i16; BUFFER_SIZE * args.channels as usize];
+                fill_sine_buffer_s16(&mut buf, freq, args.rate, args.channels, args.amplitude);
+                for _ in 0..writes {
+                    assert_eq!(
+                        io.writei(&buf)
+                            .context("Failed to write sine wave value to audio buffer")?,
+                        BUFFER_SIZE
+                    );
+                }
+            }
+            Self::S32(io) => {
+                let mut buf = vec![0i32; BUFFER_SIZE * args.channels as usize];
+                fill_sine_buffer_s32(&mut buf, freq, args.rate, args.channels, args.amplitude);
+                for _ in 0..writes {
+                    assert_eq!(
+                        io.writei(&buf)
+                            .context("Failed to write sine wave value to audio buffer")?,
+                        BUFFER_SIZE
+                    );
+                }
+            }
+            Self::U8(io) => {
+                let mut buf = vec![0u8; BUFFER_SIZE * args.channels as usize];
+                fill_sine_buffer_u8(&mut buf, freq, args.rate, args.channels, args.amplitude);
+                for _ in 0..writes {
+                    assert_eq!(
+                        io.writei(&buf)
+                            .context("Failed to write sine wave value to audio buffer")?,
+                        BUFFER_SIZE
+                    );
+                }
+            }
+        }
+
+        Ok(())
+    }
+
+    /// Stream `wav`'s raw PCM `data` out, `BUFFER_SIZE` frames at a time. Unlike `play_tone`,
+    /// the sample count isn't a multiple of `BUFFER_SIZE`, so writes loop until the whole file
+    /// has been written rather than asserting on a fixed frame count.
+    fn play_wav(&self, wav: &wav::WavData) -> anyhow::Result<()> {
+        match self {
+            Self::S16(io) => {
+                let samples: Vec<i16> = wav
+                    .data
+                    .chunks_exact(2)
+                    .map(|b| i16::from_le_bytes([b[0], b[1]]))
+                    .collect();
+                write_all(io, &samples, wav.channels as usize)?;
+            }
+            Self::S32(io) => {
+                let samples: Vec<i32> = wav
+                    .data
+                    .chunks_exact(4)
+                    .map(|b| i32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+                    .collect();
+                write_all(io, &samples, wav.channels as usize)?;
+            }
+            Self::U8(io) => {
+                write_all(io, &wav.data, wav.channels as usize)?;
+            }
+        }
+
+        Ok(())
+    }
+}
+
+/// Write every frame in `samples` (interleaved, `channels` channels per frame) to `io`, looping
+/// until all of them have been accepted.
+fn write_all<S: Copy>(io: &IO<S>, samples: &[S], channels: usize) -> anyhow::Result<()> {
+    let mut remaining = samples;
+    while !remaining.is_empty() {
+        let frames = (remaining.len() / channels).min(BUFFER_SIZE);
+        let chunk = &remaining[..frames * channels];
+        let written = io
+            .writei(chunk)
+            .context("Failed to write WAV samples to audio buffer")?;
+        remaining = &remaining[written * channels..];
+    }
+
+    Ok(())
+}
+
+enum CapturedSamples {
+    S16(Vec<i16>),
+    S32(Vec<i32>),
+    U8(Vec<u8>),
+}
+
+impl CapturedSamples {
+    /// Normalize to one `[-1.0, 1.0]` sample per frame, taken from the first channel, regardless
+    /// of the underlying sample format. Used for Goertzel tone detection.
+    fn normalized_mono(&self, channels: u32) -> Vec<f32> {
+        let channels = channels as usize;
+        match self {
+            Self::S16(buf) => buf
+                .chunks(channels)
+                .map(|f| f[0] as f32 / i16::MAX as f32)
+                .collect(),
+            Self::S32(buf) => buf
+                .chunks(channels)
+                .map(|f| f[0] as f32 / i32::MAX as f32)
+                .collect(),
+            Self::U8(buf) => buf
+                .chunks(channels)
+                .map(|f| (f[0] as f32 - 128.0) / i8::MAX as f32)
+                .collect(),
+        }
+    }
+
+    fn bits_per_sample(&self) -> u16 {
+        match self {
+            Self::S16(_) => 16,
+            Self::S32(_) => 32,
+            Self::U8(_) => 8,
+        }
+    }
+
+    /// All channels, interleaved, as little-endian bytes, suitable for writing out as WAV `data`.
+    fn to_le_bytes(&self) -> Vec<u8> {
+        match self {
+            Self::S16(buf) => buf.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            Self::S32(buf) => buf.iter().flat_map(|s| s.to_le_bytes()).collect(),
+            Self::U8(buf) => buf.clone(),
+        }
+    }
+}
+
+enum CaptureIo<'a> {
+    S16(IO<'a, i16>),
+    S32(IO<'a, i32>),
+    U8(IO<'a, u8>),
+}
+
+impl CaptureIo<'_> {
+    fn capture(&self, frames: usize, channels: u32) -> anyhow::Result<CapturedSamples> {
+        let channels = channels as usize;
+
+        match self {
+            Self::S16(io) => {
+                let mut buf = vec![0i16; frames * channels];
+                io.readi(&mut buf)
+                    .context("Failed to read captured loopback audio")?;
+                Ok(CapturedSamples::S16(buf))
+            }
+            Self::S32(io) => {
+                let mut buf = vec![0i32; frames * channels];
+                io.readi(&mut buf)
+                    .context("Failed to read captured loopback audio")?;
+                Ok(CapturedSamples::S32(buf))
+            }
+            Self::U8(io) => {
+                let mut buf = vec![0u8; frames * channels];
+                io.readi(&mut buf)
+                    .context("Failed to read captured loopback audio")?;
+                Ok(CapturedSamples::U8(buf))
+            }
+        }
+    }
+}
+
+fn configure_hw_params(pcm: &PCM, channels: u32, rate: u32, format: Format) -> anyhow::Result<()> {
+    let hwp = HwParams::any(pcm).context("Failed to prepare hardware parameters")?;
+    hwp.set_channels(channels)
+        .context("Failed to set channels")?;
+    hwp.set_rate(rate, ValueOr::Nearest)
+        .context("Failed to set audio rate")?;
+    hwp.set_format(format)
+        .context("Failed to set audio format")?;
+    hwp.set_access(Access::RWInterleaved)
+        .context("Failed to set audio access")?;
+    pcm.hw_params(&hwp)
+        .context("Failed to set hardware parameters")?;
+
+    Ok(())
+}
+
+/// Open a capture device and record `frames` frames in `format`/`channels`. Used to verify, via
+/// loopback, that the siren (or WAV file) played back by `siren` was actually audible, and to
+/// optionally archive the recording.
+fn capture_loopback(
+    device: &str,
+    channels: u32,
+    rate: u32,
+    format: SampleFormat,
+    frames: usize,
+) -> anyhow::Result<CapturedSamples> {
+    let pcm = PCM::new(device, Direction::Capture, false)
+        .with_context(|| format!("Failed to open capture device '{device}'"))?;
+    configure_hw_params(&pcm, channels, rate, alsa_format(format))?;
+
+    let io = match format {
+        SampleFormat::S16 => CaptureIo::S16(pcm.io_i16().context("Failed to get capture IO")?),
+        SampleFormat::S32 => CaptureIo::S32(pcm.io_i32().context("Failed to get capture IO")?),
+        SampleFormat::U8 => CaptureIo::U8(pcm.io_u8().context("Failed to get capture IO")?),
+    };
+
+    pcm.start().context("Failed to start audio capture")?;
+    io.capture(frames, channels)
+}
+
+/// Verify that each frequency in `args.tone_freq` is present in `captured`, which is assumed to
+/// hold the concatenated recording of the siren played back by `siren`. Returns an error naming
+/// the first missing tone, if any.
+///
+/// This assumes the capture and playback streams started close enough in time that each tone's
+/// window lands in its own equal-length block; nothing actively aligns them (see the comment on
+/// the capture thread in `siren`). `TONE_DETECTION_THRESHOLD` and the default 1s-per-tone
+/// duration leave enough margin to absorb typical ALSA startup latency, but a slow enough start
+/// could shift a tone across a block boundary and produce a false negative.
+fn verify_tones(captured: &[f32], args: &Args) -> anyhow::Result<()> {
+    let samples_per_tone = captured.len() / args.tone_freq.len();
+
+    for (i, &freq) in args.tone_freq.iter().enumerate() {
+        let block = &captured[i * samples_per_tone..(i + 1) * samples_per_tone];
+        let ratio = tone_energy_ratio(block, freq, args.rate);
+
+        println!("Tone {freq:.2} Hz: detected energy ratio {ratio:.3}");
+
+        if ratio < TONE_DETECTION_THRESHOLD {
+            bail!(
+                "Tone {freq:.2} Hz was not detected in the captured loopback audio (ratio: \
+                 {ratio:.3}, expected >= {TONE_DETECTION_THRESHOLD})"
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Print the name of every ALSA PCM device available for playback or capture, so an operator can
+/// pick the one to pass to `--device` (e.g. the HDMI audio sink on a station with multiple sound
+/// cards) instead of relying on whatever ALSA routes as "default".
+pub(crate) fn list_devices() -> anyhow::Result<()> {
+    let hints = HintIter::new_str(None, "pcm").context("Failed to enumerate ALSA devices")?;
+
+    for hint in hints {
+        let Some(name) = hint.name else {
+            continue;
+        };
+        match hint.desc {
+            Some(desc) => println!("{name}\t{desc}"),
+            None => println!("{name}"),
+        }
+    }
+
+    Ok(())
+}
+
+// https://docs.rs/alsa/0.7.0/alsa/pcm/index.html
+/// Sound a siren over the ALSA device named by `args.device` and verify, via a loopback capture, that it was
+/// actually produced. The siren consists of the tones in `args.tone_freq`, each being played
+/// back for `args.duration` seconds, unless `args.play_file` names a WAV file to play instead
+/// (in which case its own format takes precedence and tone verification is skipped).
+pub(crate) fn siren(args: &Args) -> anyhow::Result<()> {
+    let play_wav = args.play_file.as_deref().map(wav::read).transpose()?;
+
+    let (channels, rate, format) = match &play_wav {
+        Some(wav) => (
+            wav.channels as u32,
+            wav.sample_rate,
+            sample_format_for_bits(wav.bits_per_sample)?,
+        ),
+        None => (args.channels, args.rate, args.format),
+    };
+
+    let pcm = PCM::new(&args.device, Direction::Playback, false)
+        .with_context(|| format!("Failed to open playback device '{}'", args.device))?;
+    let info = pcm.info().context("Failed to get playback device info")?;
+    let name = info
+        .get_name()
+        .context("Failed to get playback device name")?;
+    println!("Device: {name}");
+
+    configure_hw_params(&pcm, channels, rate, alsa_format(format))?;
+    let io = match format {
+        SampleFormat::S16 => PlaybackIo::S16(pcm.io_i16().context("Failed to get audio IO")?),
+        SampleFormat::S32 => PlaybackIo::S32(pcm.io_i32().context("Failed to get audio IO")?),
+        SampleFormat::U8 => PlaybackIo::U8(pcm.io_u8().context("Failed to get audio IO")?),
+    };
+
+    // Make sure we don't start the stream too early
+    let hwp = pcm
+        .hw_params_current()
+        .context("Failed to get current audio hardware parameters")?;
+    let swp = pcm
+        .sw_params_current()
+        .context("Failed to get current audio software parameters")?;
+    swp.set_start_threshold(
+        hwp.get_buffer_size()
+            .context("Failed to get hardware audio buffer size")?,
+    )
+    .context("Failed to set audio start threshold")?;
+    pcm.sw_params(&swp)
+        .context("Failed to set audio software parameters")?;
+
+    // Record the loopback in parallel with playback so the capture covers the whole siren.
+    // `capture_loopback` starts its PCM as soon as this thread is scheduled, with no
+    // synchronization to when the playback side below actually begins producing audio; any
+    // startup latency between the two shifts the recorded tones within the capture buffer
+    // (see `verify_tones`).
+    let capture_frames = match &play_wav {
+        Some(wav) => wav.data.len() / (channels as usize * (format_bytes(format))),
+        None => args.duration as usize * rate as usize * args.tone_freq.len(),
+    };
+    let device = args.device.clone();
+    let capture_thread =
+        thread::spawn(move || capture_loopback(&device, channels, rate, format, capture_frames));
+
+    match &play_wav {
+        Some(wav) => io.play_wav(wav).context("Failed to play WAV file")?,
+        None => {
+            for &freq in &args.tone_freq {
+                io.play_tone(args, freq).context("Failed to play sine wave")?;
+            }
+        }
+    }
+
+    if pcm.state() != State::Running {
+        pcm.start().context("Failed to start audio playback")?
+    };
+    // Wait for the stream to finish playback.
+    pcm.drain().context("Failed to play audio")?;
+
+    let captured = capture_thread
+        .join()
+        .map_err(|_| anyhow::anyhow!("Unable to join audio capture thread"))?
+        .context("Failed to capture loopback audio")?;
+
+    if let Some(path) = &args.record_file {
+        wav::write(
+            path,
+            channels as u16,
+            rate,
+            captured.bits_per_sample(),
+            &captured.to_le_bytes(),
+        )
+        .context("Failed to write captured loopback audio to WAV file")?;
+    }
+
+    if play_wav.is_none() {
+        let normalized = captured.normalized_mono(channels);
+        verify_tones(&normalized, args).context("Audio loopback verification failed")?;
+    }
+
+    Ok(())
+}
+
+fn format_bytes(format: SampleFormat) -> usize {
+    match format {
+        SampleFormat::U8 => 1,
+        SampleFormat::S16 => 2,
+        SampleFormat::S32 => 4,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn normalized_mono_takes_first_channel_s16() {
+        // Two channels, two frames: (i16::MAX, 0), (i16::MIN, 0)
+        let captured = CapturedSamples::S16(vec![i16::MAX, 0, i16::MIN, 0]);
+        let normalized = captured.normalized_mono(2);
+        assert_eq!(normalized.len(), 2);
+        assert!((normalized[0] - 1.0).abs() < 1e-3);
+        assert!((normalized[1] - (-1.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn normalized_mono_takes_first_channel_s32() {
+        let captured = CapturedSamples::S32(vec![i32::MAX, 0, i32::MIN, 0]);
+        let normalized = captured.normalized_mono(2);
+        assert_eq!(normalized.len(), 2);
+        assert!((normalized[0] - 1.0).abs() < 1e-3);
+        assert!((normalized[1] - (-1.0)).abs() < 1e-3);
+    }
+
+    #[test]
+    fn normalized_mono_centers_u8_on_zero() {
+        // U8 is unsigned and centered on 128; 255 and 1 should sit near +1.0 and -1.0.
+        let captured = CapturedSamples::U8(vec![255, 0, 1, 0]);
+        let normalized = captured.normalized_mono(2);
+        assert_eq!(normalized.len(), 2);
+        assert!(normalized[0] > 0.9);
+        assert!(normalized[1] < -0.9);
+    }
+
+    #[test]
+    fn tone_energy_ratio_detects_sine_in_normalized_capture() {
+        let rate = 44100;
+        let freq = 1000.0;
+        let samples: Vec<i16> = (0..4096)
+            .map(|i| {
+                let phase = 2.0 * std::f32::consts::PI * freq * i as f32 / rate as f32;
+                (phase.sin() * i16::MAX as f32) as i16
+            })
+            .collect();
+        let captured = CapturedSamples::S16(samples);
+        let normalized = captured.normalized_mono(1);
+        let ratio = tone_energy_ratio(&normalized, freq, rate);
+        assert!(ratio > 0.9, "expected a dominant tone, got ratio {ratio}");
+    }
+}
@@ -0,0 +1,70 @@
+/*
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ * SPDX-FileCopyrightText: Copyright 2024 KUNBUS GmbH
+ */
+
+//! Read back the framebuffer after a frame is displayed and dump it to a PNG, so the video test
+//! can be verified automatically instead of relying on an operator watching the panel.
+
+use anyhow::Context;
+use framebuffer::Framebuffer;
+use std::path::Path;
+
+/// Inverse of the RGB888 -> RGB565 conversion used when filling frames.
+fn rgb565_to_rgb888(pixel: u16) -> (u8, u8, u8) {
+    let r = ((pixel >> 11) & 0x1F) as u8;
+    let g = ((pixel >> 5) & 0x3F) as u8;
+    let b = (pixel & 0x1F) as u8;
+
+    (r << 3, g << 2, b << 3)
+}
+
+fn read_pixel(frame: &[u8], offset: usize, bytespp: u32) -> (u8, u8, u8) {
+    match bytespp {
+        2 => rgb565_to_rgb888(u16::from_le_bytes(frame[offset..offset + 2].try_into().unwrap())),
+        4 => {
+            let value = u32::from_le_bytes(frame[offset..offset + 4].try_into().unwrap());
+            (
+                ((value >> 16) & 0xFF) as u8,
+                ((value >> 8) & 0xFF) as u8,
+                (value & 0xFF) as u8,
+            )
+        }
+        other => panic!("{other} bytes per pixel is not supported"),
+    }
+}
+
+/// Convert a raw framebuffer capture into a tightly-packed RGB8 buffer, dropping the scanline
+/// padding `line_length` may add beyond `width * bytespp`.
+fn to_rgb8(frame: &[u8], width: u32, height: u32, line_length: u32, bytespp: u32) -> Vec<u8> {
+    let mut rgb8 = Vec::with_capacity((width * height * 3) as usize);
+
+    for y in 0..height {
+        let row_start = (y * line_length) as usize;
+        for x in 0..width {
+            let (r, g, b) = read_pixel(frame, row_start + (x * bytespp) as usize, bytespp);
+            rgb8.extend_from_slice(&[r, g, b]);
+        }
+    }
+
+    rgb8
+}
+
+/// Read back the framebuffer's current contents and write them to `dir/<name>.png`, converting
+/// from the active pixel format (RGB565 or RGB888) to RGB8.
+pub(crate) fn capture(
+    framebuffer: &mut Framebuffer,
+    width: u32,
+    height: u32,
+    line_length: u32,
+    bytespp: u32,
+    dir: &Path,
+    name: &str,
+) -> anyhow::Result<()> {
+    let frame = framebuffer.read_frame();
+    let rgb8 = to_rgb8(frame, width, height, line_length, bytespp);
+
+    let path = dir.join(format!("{name}.png"));
+    image::save_buffer(&path, &rgb8, width, height, image::ColorType::Rgb8)
+        .with_context(|| format!("Failed to write framebuffer capture to '{}'", path.display()))
+}
@@ -1,10 +1,84 @@
 /*
  * SPDX-License-Identifier: GPL-2.0-or-later
- * SPDX-FileCopyrightText: Copyright 2023 KUNBUS GmbH
+ * SPDX-FileCopyrightText: Copyright 2023-2024 KUNBUS GmbH
  */
 
-use clap::Parser;
+use clap::{Parser, ValueEnum};
+use std::path::PathBuf;
 
-#[derive(Parser, Debug)]
+#[derive(Parser, Debug, Clone)]
 #[command(author, version, about, long_about = None)]
-pub(crate) struct Args {}
+pub(crate) struct Args {
+    /// Sample rate, in Hz, used for both playback and loopback capture
+    #[arg(long, default_value_t = 44100)]
+    pub(crate) rate: u32,
+
+    /// Number of audio channels used for both playback and loopback capture
+    #[arg(long, default_value_t = 1, value_parser = clap::value_parser!(u32).range(1..))]
+    pub(crate) channels: u32,
+
+    /// Sample format used for both playback and loopback capture
+    #[arg(long, value_enum, default_value_t = SampleFormat::S16)]
+    pub(crate) format: SampleFormat,
+
+    /// Frequency, in Hz, of a tone to play. May be given multiple times to play several tones in
+    /// sequence, as the siren does by default.
+    #[arg(long = "tone-freq", num_args = 1.., default_values_t = [344.53, 689.06, 1033.59])]
+    pub(crate) tone_freq: Vec<f32>,
+
+    /// Amplitude of the generated sine wave, as a fraction of the sample format's full scale
+    #[arg(long, default_value_t = 0.25)]
+    pub(crate) amplitude: f32,
+
+    /// How long each tone is played back for, in seconds
+    #[arg(long, default_value_t = 1)]
+    pub(crate) duration: u64,
+
+    /// Play back a WAV file instead of the synthesized siren. Its `fmt` chunk overrides
+    /// `--rate`, `--channels`, and `--format` for the playback device.
+    #[arg(long)]
+    pub(crate) play_file: Option<PathBuf>,
+
+    /// Write the captured loopback recording to a WAV file for later inspection
+    #[arg(long)]
+    pub(crate) record_file: Option<PathBuf>,
+
+    /// Video test pattern to display on the framebuffer
+    #[arg(long, value_enum, default_value_t = Pattern::Solid)]
+    pub(crate) pattern: Pattern,
+
+    /// List available ALSA playback/capture device names and exit
+    #[arg(long)]
+    pub(crate) list_devices: bool,
+
+    /// ALSA device to use for playback and loopback capture, e.g. the HDMI audio sink on
+    /// stations with multiple sound cards
+    #[arg(long, default_value = "default")]
+    pub(crate) device: String,
+
+    /// Directory to dump a PNG snapshot of the framebuffer into after each frame is displayed,
+    /// for automated visual verification
+    #[arg(long)]
+    pub(crate) capture_dir: Option<PathBuf>,
+}
+
+/// Sample formats the test can drive ALSA playback/capture with.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SampleFormat {
+    S16,
+    S32,
+    U8,
+}
+
+/// Video test patterns that can be shown on the framebuffer.
+#[derive(ValueEnum, Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum Pattern {
+    /// The original solid Red/Green/Blue cycle
+    Solid,
+    /// SMPTE-style vertical color bars
+    ColorBars,
+    /// Horizontal luminance gradient, black to white
+    Gradient,
+    /// Fine 1px checkerboard
+    Checkerboard,
+}
@@ -0,0 +1,96 @@
+/*
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ * SPDX-FileCopyrightText: Copyright 2024 KUNBUS GmbH
+ */
+
+//! Goertzel algorithm for detecting the presence of a single target frequency in a block of
+//! audio samples. Used to verify, from a loopback recording, that a tone we played back was
+//! actually audible.
+
+/// Compute the energy of `target_freq` within `samples`, a mono block of samples (normalized to
+/// `[-1.0, 1.0]`, independent of the original sample format) taken at `sample_rate`, normalized
+/// by the total energy of the block. The result can be compared against a threshold regardless
+/// of the absolute volume of the recording: it tends towards 1.0 the more the block is dominated
+/// by `target_freq` and towards 0.0 when the frequency is absent.
+pub(crate) fn tone_energy_ratio(samples: &[f32], target_freq: f32, sample_rate: u32) -> f32 {
+    let n = samples.len();
+    if n == 0 {
+        return 0.0;
+    }
+
+    let k = (n as f32 * target_freq / sample_rate as f32).round();
+    let omega = 2.0 * std::f32::consts::PI * k / n as f32;
+    let coeff = 2.0 * omega.cos();
+
+    let mut s_prev = 0.0f32;
+    let mut s_prev2 = 0.0f32;
+    let mut energy = 0.0f32;
+
+    for &x in samples {
+        let s = x + coeff * s_prev - s_prev2;
+        s_prev2 = s_prev;
+        s_prev = s;
+        energy += x * x;
+    }
+
+    let magnitude_sq = s_prev * s_prev + s_prev2 * s_prev2 - coeff * s_prev * s_prev2;
+
+    // A pure sine at bin `k` has `magnitude_sq == energy * n / 2`, so scale by that to map it
+    // to 1.0.
+    if energy == 0.0 {
+        0.0
+    } else {
+        magnitude_sq / (energy * n as f32 / 2.0)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const SAMPLE_RATE: u32 = 44100;
+
+    fn sine(freq: f32, n: usize) -> Vec<f32> {
+        (0..n)
+            .map(|i| (2.0 * std::f32::consts::PI * freq * i as f32 / SAMPLE_RATE as f32).sin())
+            .collect()
+    }
+
+    #[test]
+    fn detects_tone_at_target_frequency() {
+        let samples = sine(1000.0, 4096);
+        let ratio = tone_energy_ratio(&samples, 1000.0, SAMPLE_RATE);
+        assert!(ratio > 0.9, "expected a dominant tone, got ratio {ratio}");
+    }
+
+    #[test]
+    fn rejects_silence() {
+        let samples = vec![0.0f32; 4096];
+        let ratio = tone_energy_ratio(&samples, 1000.0, SAMPLE_RATE);
+        assert_eq!(ratio, 0.0);
+    }
+
+    #[test]
+    fn rejects_off_frequency_tone() {
+        let samples = sine(1000.0, 4096);
+        let ratio = tone_energy_ratio(&samples, 4000.0, SAMPLE_RATE);
+        assert!(ratio < 0.15, "expected a weak match, got ratio {ratio}");
+    }
+
+    #[test]
+    fn rejects_white_noise() {
+        // A simple LCG stands in for noise without pulling in a RNG crate.
+        let mut state = 1u32;
+        let samples: Vec<f32> = (0..4096)
+            .map(|_| {
+                state = state.wrapping_mul(1103515245).wrapping_add(12345);
+                (state >> 16) as f32 / u16::MAX as f32 * 2.0 - 1.0
+            })
+            .collect();
+        let ratio = tone_energy_ratio(&samples, 1000.0, SAMPLE_RATE);
+        assert!(
+            ratio < 0.15,
+            "expected noise to have no dominant tone, got ratio {ratio}"
+        );
+    }
+}
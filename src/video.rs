@@ -0,0 +1,283 @@
+/*
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ * SPDX-FileCopyrightText: Copyright 2023-2024 KUNBUS GmbH
+ */
+
+//! Show test frames on the framebuffer: either the original solid Red/Green/Blue cycle, or a
+//! pattern (color bars, gradient, checkerboard) chosen to expose dead pixels, stuck subpixels,
+//! backlight nonuniformity, and scaling errors.
+
+use anyhow::Context;
+use framebuffer::{Framebuffer, KdMode};
+use std::{thread, time};
+
+use crate::capture;
+use crate::cli::{Args, Pattern};
+
+const TTY: &str = "/dev/tty1";
+const FB: &str = "/dev/fb0";
+
+// how long a frame is displayed in seconds
+const FRAME_LENGTH: u64 = 1;
+
+enum Color {
+    Red,
+    Green,
+    Blue,
+}
+
+// `From` doesn't make sense as we're only trying to represent 3 colors
+#[allow(clippy::from_over_into)]
+impl Into<[u8; 4]> for Color {
+    fn into(self) -> [u8; 4] {
+        match self {
+            Self::Red => 0xFFu32 << 16,
+            Self::Green => 0xFFu32 << 8,
+            Self::Blue => 0xFFu32,
+        }
+        .to_le_bytes()
+    }
+}
+
+#[allow(clippy::from_over_into)]
+impl Into<[u8; 2]> for Color {
+    fn into(self) -> [u8; 2] {
+        match self {
+            Self::Red => rgb888_to_rgb565(0xFF, 0, 0),
+            Self::Green => rgb888_to_rgb565(0, 0xFF, 0),
+            Self::Blue => rgb888_to_rgb565(0, 0, 0xFF),
+        }
+        .to_le_bytes()
+    }
+}
+
+/// Convert a RGB888 color to RGB565
+const fn rgb888_to_rgb565(red: u8, green: u8, blue: u8) -> u16 {
+    let r = (red >> 3) as u16;
+    let g = (green >> 2) as u16;
+    let b = (blue >> 3) as u16;
+
+    (r << 11) | (g << 5) | b
+}
+
+fn frame_set_color(frame: &mut [u8], color: Color, bytespp: u32) {
+    match bytespp {
+        2 => {
+            let color: [u8; 2] = color.into();
+            let mut color = color.iter().cycle().peekable();
+            assert!(color.peek().is_some());
+            frame.fill_with(|| *color.next().expect("BUG: 2-width color is empty"));
+        }
+        4 => {
+            let color: [u8; 4] = color.into();
+            let mut color = color.iter().cycle().peekable();
+            assert!(color.peek().is_some());
+            frame.fill_with(|| *color.next().expect("BUG: 4-width color is empty"));
+        }
+        other => panic!("{other} bytes per pixel is not supported"),
+    }
+}
+
+/// Write a single pixel's RGB888 value into `frame` at byte offset `offset`, encoding it as
+/// RGB565 or RGB888 depending on `bytespp`.
+fn write_pixel(frame: &mut [u8], offset: usize, r: u8, g: u8, b: u8, bytespp: u32) {
+    match bytespp {
+        2 => frame[offset..offset + 2].copy_from_slice(&rgb888_to_rgb565(r, g, b).to_le_bytes()),
+        4 => frame[offset..offset + 4]
+            .copy_from_slice(&(((r as u32) << 16) | ((g as u32) << 8) | b as u32).to_le_bytes()),
+        other => panic!("{other} bytes per pixel is not supported"),
+    }
+}
+
+/// Fill `frame` line-by-line with a pattern, calling `pixel(x, y)` for every pixel to get its
+/// RGB888 value. Respects `line_length`, which may be wider than `width * bytespp` due to
+/// scanline padding.
+fn fill_pattern(
+    frame: &mut [u8],
+    width: u32,
+    height: u32,
+    line_length: u32,
+    bytespp: u32,
+    mut pixel: impl FnMut(u32, u32) -> (u8, u8, u8),
+) {
+    for y in 0..height {
+        let row_start = (y * line_length) as usize;
+        for x in 0..width {
+            let (r, g, b) = pixel(x, y);
+            let offset = row_start + (x * bytespp) as usize;
+            write_pixel(frame, offset, r, g, b, bytespp);
+        }
+    }
+}
+
+// SMPTE-style vertical color bars, left to right.
+const COLOR_BARS: [(u8, u8, u8); 7] = [
+    (0xFF, 0xFF, 0xFF), // white
+    (0xFF, 0xFF, 0x00), // yellow
+    (0x00, 0xFF, 0xFF), // cyan
+    (0x00, 0xFF, 0x00), // green
+    (0xFF, 0x00, 0xFF), // magenta
+    (0xFF, 0x00, 0x00), // red
+    (0x00, 0x00, 0xFF), // blue
+];
+
+fn fill_color_bars(frame: &mut [u8], width: u32, height: u32, line_length: u32, bytespp: u32) {
+    fill_pattern(frame, width, height, line_length, bytespp, |x, _y| {
+        let bar = (x * COLOR_BARS.len() as u32 / width).min(COLOR_BARS.len() as u32 - 1);
+        COLOR_BARS[bar as usize]
+    });
+}
+
+/// Horizontal luminance gradient, from black on the left to white on the right.
+fn fill_gradient(frame: &mut [u8], width: u32, height: u32, line_length: u32, bytespp: u32) {
+    fill_pattern(frame, width, height, line_length, bytespp, |x, _y| {
+        let luminance = (x * 0xFF / width) as u8;
+        (luminance, luminance, luminance)
+    });
+}
+
+/// 1px black/white checkerboard, which makes dead/hot pixels and scaling/sync issues immediately
+/// visible.
+fn fill_checkerboard(frame: &mut [u8], width: u32, height: u32, line_length: u32, bytespp: u32) {
+    fill_pattern(frame, width, height, line_length, bytespp, |x, y| {
+        if (x + y) % 2 == 0 {
+            (0xFF, 0xFF, 0xFF)
+        } else {
+            (0x00, 0x00, 0x00)
+        }
+    });
+}
+
+/// Write frame to framebuffer and wait for `FRAME_LENGTH` seconds.
+fn frame_write_color(framebuffer: &mut Framebuffer, frame: &mut [u8], color: Color, bytespp: u32) {
+    frame_set_color(frame, color, bytespp);
+    framebuffer.write_frame(frame);
+    thread::sleep(time::Duration::from_secs(FRAME_LENGTH));
+}
+
+/// Write an already-filled frame to the framebuffer and wait `secs` seconds.
+fn frame_write(framebuffer: &mut Framebuffer, frame: &[u8], secs: u64) {
+    framebuffer.write_frame(frame);
+    thread::sleep(time::Duration::from_secs(secs));
+}
+
+/// Dump a PNG snapshot of the framebuffer's current contents, named `<name>.png`, if
+/// `args.capture_dir` was given.
+fn capture_if_requested(
+    framebuffer: &mut Framebuffer,
+    width: u32,
+    height: u32,
+    line_length: u32,
+    bytespp: u32,
+    args: &Args,
+    name: &str,
+) -> anyhow::Result<()> {
+    let Some(dir) = &args.capture_dir else {
+        return Ok(());
+    };
+
+    capture::capture(framebuffer, width, height, line_length, bytespp, dir, name)
+}
+
+/// Show the video test pattern selected by `args.pattern`. The default, `Pattern::Solid`,
+/// displays 3 frames, each for `FRAME_LENGTH` seconds, alternating Red, Green, and Blue; the
+/// other patterns fill the whole screen once and hold it for `FRAME_LENGTH * 3` seconds, the
+/// same time the solid cycle takes. This does not follow `--duration`, `--tone-freq`, or
+/// `--play-file`, so it matches the siren's length only with the default tone settings.
+pub(crate) fn frame(args: &Args) -> anyhow::Result<()> {
+    let mut framebuffer =
+        Framebuffer::new(FB).with_context(|| format!("Failed to open framebuffer '{FB}'"))?;
+
+    let fb_width = framebuffer.var_screen_info.xres;
+    let fb_height = framebuffer.var_screen_info.yres;
+    let line_length = framebuffer.fix_screen_info.line_length;
+    let bytespp = framebuffer.var_screen_info.bits_per_pixel / 8;
+
+    println!("w: {fb_width}; h: {fb_height}; line_length: {line_length}; bpp: {bytespp}");
+
+    let mut frame = vec![0u8; (line_length * fb_height) as usize];
+
+    //Disable text mode for tty1
+    Framebuffer::set_kd_mode_ex(TTY, KdMode::Graphics)
+        .with_context(|| format!("Unable to disable text mode on TTY '{TTY}'"))?;
+
+    match args.pattern {
+        Pattern::Solid => {
+            frame_write_color(&mut framebuffer, &mut frame, Color::Red, bytespp);
+            capture_if_requested(
+                &mut framebuffer,
+                fb_width,
+                fb_height,
+                line_length,
+                bytespp,
+                args,
+                "red",
+            )?;
+            frame_write_color(&mut framebuffer, &mut frame, Color::Green, bytespp);
+            capture_if_requested(
+                &mut framebuffer,
+                fb_width,
+                fb_height,
+                line_length,
+                bytespp,
+                args,
+                "green",
+            )?;
+            frame_write_color(&mut framebuffer, &mut frame, Color::Blue, bytespp);
+            capture_if_requested(
+                &mut framebuffer,
+                fb_width,
+                fb_height,
+                line_length,
+                bytespp,
+                args,
+                "blue",
+            )?;
+        }
+        Pattern::ColorBars => {
+            fill_color_bars(&mut frame, fb_width, fb_height, line_length, bytespp);
+            frame_write(&mut framebuffer, &frame, FRAME_LENGTH * 3);
+            capture_if_requested(
+                &mut framebuffer,
+                fb_width,
+                fb_height,
+                line_length,
+                bytespp,
+                args,
+                "color_bars",
+            )?;
+        }
+        Pattern::Gradient => {
+            fill_gradient(&mut frame, fb_width, fb_height, line_length, bytespp);
+            frame_write(&mut framebuffer, &frame, FRAME_LENGTH * 3);
+            capture_if_requested(
+                &mut framebuffer,
+                fb_width,
+                fb_height,
+                line_length,
+                bytespp,
+                args,
+                "gradient",
+            )?;
+        }
+        Pattern::Checkerboard => {
+            fill_checkerboard(&mut frame, fb_width, fb_height, line_length, bytespp);
+            frame_write(&mut framebuffer, &frame, FRAME_LENGTH * 3);
+            capture_if_requested(
+                &mut framebuffer,
+                fb_width,
+                fb_height,
+                line_length,
+                bytespp,
+                args,
+                "checkerboard",
+            )?;
+        }
+    }
+
+    //Reenable text mode in current tty
+    Framebuffer::set_kd_mode_ex(TTY, KdMode::Text)
+        .with_context(|| format!("Unable to enable text mode on TTY '{TTY}'"))?;
+
+    Ok(())
+}
@@ -0,0 +1,263 @@
+/*
+ * SPDX-License-Identifier: GPL-2.0-or-later
+ * SPDX-FileCopyrightText: Copyright 2024 KUNBUS GmbH
+ */
+
+//! Minimal RIFF/WAVE reading and writing: just enough to stream a PCM test fixture over ALSA
+//! and to archive a captured loopback recording for later inspection.
+
+use anyhow::{bail, Context};
+use std::fs;
+use std::path::Path;
+
+/// The parts of a WAV file needed to configure ALSA playback and to get at the raw PCM samples.
+#[derive(Debug)]
+pub(crate) struct WavData {
+    pub(crate) channels: u16,
+    pub(crate) sample_rate: u32,
+    pub(crate) bits_per_sample: u16,
+    pub(crate) data: Vec<u8>,
+}
+
+/// Parse a RIFF/WAVE file's `fmt ` and `data` chunks. Only uncompressed PCM (format tag `1`) is
+/// supported.
+pub(crate) fn read(path: &Path) -> anyhow::Result<WavData> {
+    let bytes =
+        fs::read(path).with_context(|| format!("Failed to read WAV file '{}'", path.display()))?;
+
+    if bytes.len() < 12 || &bytes[0..4] != b"RIFF" || &bytes[8..12] != b"WAVE" {
+        bail!("'{}' is not a valid RIFF/WAVE file", path.display());
+    }
+
+    let mut channels = None;
+    let mut sample_rate = None;
+    let mut bits_per_sample = None;
+    let mut data = None;
+
+    let mut pos = 12;
+    while pos + 8 <= bytes.len() {
+        let id = &bytes[pos..pos + 4];
+        let size = u32::from_le_bytes(bytes[pos + 4..pos + 8].try_into().unwrap()) as usize;
+        let body_start = pos + 8;
+        let body_end = body_start + size;
+        if body_end > bytes.len() {
+            bail!("'{}' has a truncated chunk", path.display());
+        }
+        let body = &bytes[body_start..body_end];
+
+        match id {
+            b"fmt " => {
+                if body.len() < 16 {
+                    bail!("'{}' has a truncated fmt chunk", path.display());
+                }
+                let format_tag = u16::from_le_bytes(body[0..2].try_into().unwrap());
+                if format_tag != 1 {
+                    bail!(
+                        "'{}' uses WAV format tag {format_tag}, only uncompressed PCM (1) is \
+                         supported",
+                        path.display()
+                    );
+                }
+                channels = Some(u16::from_le_bytes(body[2..4].try_into().unwrap()));
+                sample_rate = Some(u32::from_le_bytes(body[4..8].try_into().unwrap()));
+                bits_per_sample = Some(u16::from_le_bytes(body[14..16].try_into().unwrap()));
+            }
+            b"data" => data = Some(body.to_vec()),
+            _ => {}
+        }
+
+        // chunks are word-aligned
+        pos = body_end + (size % 2);
+    }
+
+    let channels = channels.with_context(|| format!("'{}' has no fmt chunk", path.display()))?;
+    let sample_rate =
+        sample_rate.with_context(|| format!("'{}' has no fmt chunk", path.display()))?;
+    let bits_per_sample =
+        bits_per_sample.with_context(|| format!("'{}' has no fmt chunk", path.display()))?;
+    let data = data.with_context(|| format!("'{}' has no data chunk", path.display()))?;
+
+    // A partial trailing frame would make playback's write_all loop forever retrying the same
+    // leftover bytes, so reject it here instead.
+    let block_align = channels as usize * (bits_per_sample as usize / 8);
+    if block_align == 0 || data.len() % block_align != 0 {
+        bail!(
+            "'{}' has a data chunk of {} bytes, not a multiple of the {block_align}-byte frame \
+             size for {channels} channel(s) at {bits_per_sample} bits per sample",
+            path.display(),
+            data.len()
+        );
+    }
+
+    Ok(WavData {
+        channels,
+        sample_rate,
+        bits_per_sample,
+        data,
+    })
+}
+
+/// Write raw PCM `data` (already interleaved in the target format) out as a RIFF/WAVE file.
+pub(crate) fn write(
+    path: &Path,
+    channels: u16,
+    sample_rate: u32,
+    bits_per_sample: u16,
+    data: &[u8],
+) -> anyhow::Result<()> {
+    let block_align = channels * (bits_per_sample / 8);
+    let byte_rate = sample_rate * block_align as u32;
+
+    let mut bytes = Vec::with_capacity(44 + data.len());
+    bytes.extend_from_slice(b"RIFF");
+    bytes.extend_from_slice(&(36 + data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(b"WAVE");
+
+    bytes.extend_from_slice(b"fmt ");
+    bytes.extend_from_slice(&16u32.to_le_bytes());
+    bytes.extend_from_slice(&1u16.to_le_bytes()); // PCM
+    bytes.extend_from_slice(&channels.to_le_bytes());
+    bytes.extend_from_slice(&sample_rate.to_le_bytes());
+    bytes.extend_from_slice(&byte_rate.to_le_bytes());
+    bytes.extend_from_slice(&block_align.to_le_bytes());
+    bytes.extend_from_slice(&bits_per_sample.to_le_bytes());
+
+    bytes.extend_from_slice(b"data");
+    bytes.extend_from_slice(&(data.len() as u32).to_le_bytes());
+    bytes.extend_from_slice(data);
+
+    fs::write(path, bytes)
+        .with_context(|| format!("Failed to write WAV file '{}'", path.display()))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicU32, Ordering};
+
+    // Each test gets its own file under the OS temp dir, since `read`/`write` only work on disk.
+    fn temp_path(name: &str) -> std::path::PathBuf {
+        static COUNTER: AtomicU32 = AtomicU32::new(0);
+        let n = COUNTER.fetch_add(1, Ordering::Relaxed);
+        std::env::temp_dir().join(format!("eol-test-hdmi-wav-test-{name}-{n}.wav"))
+    }
+
+    fn fmt_chunk(format_tag: u16, channels: u16, sample_rate: u32, bits_per_sample: u16) -> Vec<u8> {
+        let block_align = channels * (bits_per_sample / 8);
+        let byte_rate = sample_rate * block_align as u32;
+        let mut body = Vec::new();
+        body.extend_from_slice(&format_tag.to_le_bytes());
+        body.extend_from_slice(&channels.to_le_bytes());
+        body.extend_from_slice(&sample_rate.to_le_bytes());
+        body.extend_from_slice(&byte_rate.to_le_bytes());
+        body.extend_from_slice(&block_align.to_le_bytes());
+        body.extend_from_slice(&bits_per_sample.to_le_bytes());
+        body
+    }
+
+    fn chunk(id: &[u8; 4], body: &[u8]) -> Vec<u8> {
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(id);
+        bytes.extend_from_slice(&(body.len() as u32).to_le_bytes());
+        bytes.extend_from_slice(body);
+        if !body.len().is_multiple_of(2) {
+            bytes.push(0);
+        }
+        bytes
+    }
+
+    fn riff(chunks: &[Vec<u8>]) -> Vec<u8> {
+        let body_len: usize = 4 + chunks.iter().map(Vec::len).sum::<usize>();
+        let mut bytes = Vec::new();
+        bytes.extend_from_slice(b"RIFF");
+        bytes.extend_from_slice(&(body_len as u32).to_le_bytes());
+        bytes.extend_from_slice(b"WAVE");
+        for c in chunks {
+            bytes.extend_from_slice(c);
+        }
+        bytes
+    }
+
+    #[test]
+    fn round_trip_preserves_pcm_data() {
+        let path = temp_path("round-trip");
+        let data = vec![1, 2, 3, 4, 5, 6, 7, 8];
+        write(&path, 2, 44100, 16, &data).unwrap();
+
+        let wav = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(wav.channels, 2);
+        assert_eq!(wav.sample_rate, 44100);
+        assert_eq!(wav.bits_per_sample, 16);
+        assert_eq!(wav.data, data);
+    }
+
+    #[test]
+    fn skips_odd_sized_chunk_before_fmt_and_data() {
+        let path = temp_path("odd-chunk");
+        let bytes = riff(&[
+            chunk(b"JUNK", &[0xAA, 0xBB, 0xCC]),
+            chunk(b"fmt ", &fmt_chunk(1, 1, 8000, 8)),
+            chunk(b"data", &[10, 20, 30]),
+        ]);
+        fs::write(&path, bytes).unwrap();
+
+        let wav = read(&path).unwrap();
+        fs::remove_file(&path).unwrap();
+
+        assert_eq!(wav.channels, 1);
+        assert_eq!(wav.data, vec![10, 20, 30]);
+    }
+
+    #[test]
+    fn rejects_missing_fmt_chunk() {
+        let path = temp_path("no-fmt");
+        let bytes = riff(&[chunk(b"data", &[1, 2, 3, 4])]);
+        fs::write(&path, bytes).unwrap();
+
+        let err = read(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("no fmt chunk"));
+    }
+
+    #[test]
+    fn rejects_missing_data_chunk() {
+        let path = temp_path("no-data");
+        let bytes = riff(&[chunk(b"fmt ", &fmt_chunk(1, 1, 8000, 8))]);
+        fs::write(&path, bytes).unwrap();
+
+        let err = read(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("no data chunk"));
+    }
+
+    #[test]
+    fn rejects_unsupported_format_tag() {
+        let path = temp_path("bad-format-tag");
+        let bytes = riff(&[
+            chunk(b"fmt ", &fmt_chunk(3, 1, 8000, 32)), // 3 == IEEE float, unsupported
+            chunk(b"data", &[0, 0, 0, 0]),
+        ]);
+        fs::write(&path, bytes).unwrap();
+
+        let err = read(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("format tag 3"));
+    }
+
+    #[test]
+    fn rejects_data_not_a_multiple_of_the_frame_size() {
+        let path = temp_path("partial-frame");
+        // 2 channels at 16 bits per sample means a 4-byte frame; 6 bytes leaves a partial frame.
+        let bytes = riff(&[
+            chunk(b"fmt ", &fmt_chunk(1, 2, 44100, 16)),
+            chunk(b"data", &[0, 0, 0, 0, 0, 0]),
+        ]);
+        fs::write(&path, bytes).unwrap();
+
+        let err = read(&path).unwrap_err();
+        fs::remove_file(&path).unwrap();
+        assert!(err.to_string().contains("not a multiple"));
+    }
+}